@@ -0,0 +1,104 @@
+use std::path::Path;
+
+use anyhow::{Context, anyhow};
+use git2::{DiffLineType, DiffOptions, Repository};
+
+use super::{DiffRequest, GitBackend};
+
+/// Default backend, built on `git2` (libgit2).
+pub struct Git2Backend(Repository);
+
+impl GitBackend for Git2Backend {
+    fn open(path: &Path) -> anyhow::Result<Self> {
+        Repository::open(path)
+            .map(Git2Backend)
+            .with_context(|| format!("Could not find repository at {path:?}"))
+    }
+
+    fn resolve(&self, rev: &str) -> anyhow::Result<String> {
+        let commit = self.0.revparse_single(rev)?.peel_to_commit()?;
+        Ok(commit.id().to_string())
+    }
+
+    fn read_blob(&self, rev: &str, path: &str) -> anyhow::Result<String> {
+        let commit = self.0.revparse_single(rev)?.peel_to_commit()?;
+
+        let tree = commit.tree()?;
+        let entry = tree.get_path(Path::new(path))?;
+
+        let object = entry.to_object(&self.0)?;
+        let blob = object
+            .as_blob()
+            .ok_or_else(|| anyhow!("Commit does not contain this file."))?;
+
+        std::str::from_utf8(blob.content())
+            .map(ToOwned::to_owned)
+            .map_err(Into::into)
+    }
+
+    fn diff(&self, req: DiffRequest) -> anyhow::Result<String> {
+        let old_commit = self.0.revparse_single(req.old)?.peel_to_commit()?;
+        let new_commit = self.0.revparse_single(req.new)?.peel_to_commit()?;
+
+        let old_tree = old_commit.tree()?;
+        let new_tree = new_commit.tree()?;
+
+        let mut diff_opts = DiffOptions::new();
+        diff_opts.pathspec(req.path);
+
+        if let Some(context_lines) = req.context_lines {
+            diff_opts.context_lines(context_lines);
+        }
+
+        let diff =
+            self.0
+                .diff_tree_to_tree(Some(&old_tree), Some(&new_tree), Some(&mut diff_opts))?;
+
+        if req.stat {
+            let stats = diff.stats()?;
+            let buf = stats.to_buf(git2::DiffStatsFormat::FULL, 80)?;
+            return Ok(buf.as_str().unwrap_or_default().to_owned());
+        }
+
+        let mut str = String::new();
+
+        diff.print(git2::DiffFormat::Patch, |_delta, _hunk, line| {
+            let mut content = std::str::from_utf8(line.content())
+                .expect("utf8 expected")
+                .to_owned();
+
+            content = content
+                .lines()
+                .map(|content| {
+                    format!(
+                        "{}{}{content}\n",
+                        (req.hide_deletions
+                            && !matches!(
+                                line.origin_value(),
+                                DiffLineType::Addition | DiffLineType::Context
+                            ))
+                        .then_some(req.hidden_prefix)
+                        .unwrap_or_default(),
+                        (matches!(
+                            line.origin_value(),
+                            DiffLineType::Addition | DiffLineType::Deletion | DiffLineType::Context
+                        ))
+                        .then(|| line.origin().to_string())
+                        .unwrap_or_default()
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join("");
+
+            str.push_str(&content);
+
+            true
+        })?;
+
+        Ok(str)
+    }
+
+    fn config_str(&self, key: &str) -> Option<String> {
+        self.0.config().ok()?.get_string(key).ok()
+    }
+}
@@ -0,0 +1,219 @@
+use std::path::Path;
+
+use anyhow::{Context, anyhow};
+use gix::bstr::ByteSlice;
+use gix::diff::blob::{
+    Algorithm,
+    intern::InternedInput,
+    unified_diff::{ContextSize, UnifiedDiffBuilder},
+};
+use gix::object::tree::diff::ChangeDetached;
+
+use super::{DiffRequest, GitBackend};
+
+/// Pure-Rust backend built on `gitoxide`, enabled via the `gix` feature so
+/// `cargo install mdbook-git` doesn't have to link libgit2.
+pub struct GixBackend(gix::Repository);
+
+impl GitBackend for GixBackend {
+    fn open(path: &Path) -> anyhow::Result<Self> {
+        gix::open(path)
+            .map(GixBackend)
+            .with_context(|| format!("Could not find repository at {path:?}"))
+    }
+
+    fn resolve(&self, rev: &str) -> anyhow::Result<String> {
+        let commit = self.0.rev_parse_single(rev)?.object()?.peel_to_commit()?;
+        Ok(commit.id.to_string())
+    }
+
+    fn read_blob(&self, rev: &str, path: &str) -> anyhow::Result<String> {
+        let commit = self.0.rev_parse_single(rev)?.object()?.peel_to_commit()?;
+
+        let tree = commit.tree()?;
+        let entry = tree
+            .lookup_entry_by_path(path)?
+            .ok_or_else(|| anyhow!("Commit does not contain this file."))?;
+
+        let blob = entry.object()?;
+
+        blob.data.to_str().map(ToOwned::to_owned).map_err(Into::into)
+    }
+
+    fn diff(&self, req: DiffRequest) -> anyhow::Result<String> {
+        let old_tree = self
+            .0
+            .rev_parse_single(req.old)?
+            .object()?
+            .peel_to_commit()?
+            .tree()?;
+        let new_tree = self
+            .0
+            .rev_parse_single(req.new)?
+            .object()?
+            .peel_to_commit()?
+            .tree()?;
+
+        let mut changes = Vec::new();
+        old_tree
+            .changes()?
+            .for_each_to_obtain_tree(&new_tree, |change| {
+                if change.location().to_str_lossy() == req.path {
+                    changes.push(change.detach());
+                }
+                Ok::<_, gix::object::tree::diff::for_each::Error>(
+                    gix::object::tree::diff::Action::Continue,
+                )
+            })?;
+
+        let context = req.context_lines.unwrap_or(3) as usize;
+        let mut patch = String::new();
+        let mut insertions = 0usize;
+        let mut deletions = 0usize;
+
+        for change in &changes {
+            let (old_content, new_content) = self.blob_pair(change)?;
+            let input = InternedInput::new(old_content.as_str(), new_content.as_str());
+            let hunk = gix::diff::blob::diff(
+                Algorithm::Histogram,
+                &input,
+                UnifiedDiffBuilder::with_context_size(&input, ContextSize::symmetrical(context)),
+            );
+
+            let (hunk_insertions, hunk_deletions) = count_changes(&hunk);
+            insertions += hunk_insertions;
+            deletions += hunk_deletions;
+
+            if req.hide_deletions {
+                patch.push_str(&hide_deletions(&hunk, req.hidden_prefix));
+            } else {
+                patch.push_str(&hunk);
+            }
+        }
+
+        if req.stat {
+            let files = changes.len();
+            return Ok(format!(
+                " {files} file{} changed, {insertions} insertion{}(+), {deletions} deletion{}(-)\n",
+                plural(files),
+                plural(insertions),
+                plural(deletions),
+            ));
+        }
+
+        Ok(patch)
+    }
+
+    fn config_str(&self, key: &str) -> Option<String> {
+        self.0
+            .config_snapshot()
+            .string(key)
+            .map(|val| val.to_str_lossy().into_owned())
+    }
+}
+
+fn plural(count: usize) -> &'static str {
+    if count == 1 { "" } else { "s" }
+}
+
+/// Count the real inserted/removed content lines in a unified diff hunk,
+/// i.e. lines starting with `+`/`-`, excluding the `+++`/`---` file headers
+/// and `@@` hunk headers that also start with those characters.
+fn count_changes(hunk: &str) -> (usize, usize) {
+    let mut insertions = 0usize;
+    let mut deletions = 0usize;
+
+    for line in hunk.lines() {
+        if line.starts_with("+++") || line.starts_with("---") || line.starts_with("@@") {
+            continue;
+        } else if line.starts_with('+') {
+            insertions += 1;
+        } else if line.starts_with('-') {
+            deletions += 1;
+        }
+    }
+
+    (insertions, deletions)
+}
+
+/// Prefix every deletion line in a unified diff hunk with `hidden_prefix`
+/// instead of printing it as a removed line.
+fn hide_deletions(hunk: &str, hidden_prefix: &str) -> String {
+    let mut patch = String::new();
+
+    for line in hunk.lines() {
+        if line.starts_with('-') {
+            patch.push_str(hidden_prefix);
+        }
+        patch.push_str(line);
+        patch.push('\n');
+    }
+
+    patch
+}
+
+impl GixBackend {
+    fn blob_pair(&self, change: &ChangeDetached) -> anyhow::Result<(String, String)> {
+        let old_content = change
+            .previous_id()
+            .map(|id| self.0.find_blob(id))
+            .transpose()?
+            .map(|blob| blob.data.to_str_lossy().into_owned())
+            .unwrap_or_default();
+
+        let new_content = change
+            .id()
+            .map(|id| self.0.find_blob(id))
+            .transpose()?
+            .map(|blob| blob.data.to_str_lossy().into_owned())
+            .unwrap_or_default();
+
+        Ok((old_content, new_content))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hunk(old: &str, new: &str) -> String {
+        let input = InternedInput::new(old, new);
+        gix::diff::blob::diff(
+            Algorithm::Histogram,
+            &input,
+            UnifiedDiffBuilder::with_context_size(&input, ContextSize::symmetrical(3)),
+        )
+    }
+
+    #[test]
+    fn count_changes_on_an_add_only_change_counts_only_insertions() {
+        let hunk = hunk("one\n", "one\ntwo\nthree\n");
+        assert_eq!(count_changes(&hunk), (2, 0));
+    }
+
+    #[test]
+    fn count_changes_on_a_delete_only_change_counts_only_deletions() {
+        let hunk = hunk("one\ntwo\nthree\n", "one\n");
+        assert_eq!(count_changes(&hunk), (0, 2));
+    }
+
+    #[test]
+    fn count_changes_on_a_mixed_change_counts_both_and_ignores_headers() {
+        let hunk = hunk("one\ntwo\nthree\n", "one\nTWO\nthree\nfour\n");
+        assert_eq!(count_changes(&hunk), (2, 1));
+    }
+
+    #[test]
+    fn hide_deletions_prefixes_only_removed_lines() {
+        let hunk = hunk("one\ntwo\nthree\n", "one\nTWO\nthree\n");
+        let patch = hide_deletions(&hunk, "XX");
+
+        for line in patch.lines() {
+            if line.starts_with("-two") {
+                assert!(line.starts_with("XX"), "deletion not prefixed: {line:?}");
+            } else if line.starts_with('+') && !line.starts_with("+++") {
+                assert!(!line.starts_with("XX+"), "addition wrongly prefixed: {line:?}");
+            }
+        }
+    }
+}
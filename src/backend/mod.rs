@@ -0,0 +1,61 @@
+use std::path::Path;
+
+#[cfg(not(feature = "gix"))]
+mod git2_backend;
+#[cfg(not(feature = "gix"))]
+pub use git2_backend::Git2Backend;
+
+#[cfg(feature = "gix")]
+mod gix_backend;
+#[cfg(feature = "gix")]
+pub use gix_backend::GixBackend;
+
+/// The default backend is `git2` (libgit2). Enabling the `gix` feature swaps
+/// in a pure-Rust `gitoxide` backend instead, dropping the libgit2 C
+/// dependency at the cost of relying on gitoxide's diff machinery.
+#[cfg(not(feature = "gix"))]
+pub type DefaultBackend = Git2Backend;
+#[cfg(feature = "gix")]
+pub type DefaultBackend = GixBackend;
+
+/// Options for [`GitBackend::diff`], gathered here so adding a knob doesn't
+/// ripple through every backend's function signature.
+pub struct DiffRequest<'a> {
+    pub old: &'a str,
+    pub new: &'a str,
+    pub path: &'a str,
+    pub context_lines: Option<u32>,
+    /// Prefix deletions with `hidden_prefix` instead of printing them as
+    /// removed lines. The `git2` backend also prefixes the file and hunk
+    /// headers; the `gix` backend leaves its `@@` hunk headers unprefixed.
+    pub hide_deletions: bool,
+    pub hidden_prefix: &'a str,
+    /// Render a change summary instead of the patch body. The `git2` backend
+    /// prints git's per-file table followed by the totals; the `gix` backend
+    /// prints only the totals line (files changed, insertions, deletions).
+    pub stat: bool,
+}
+
+/// The handful of git operations the preprocessor actually needs, abstracted
+/// so `git_show`/`git_diff` don't depend on a specific git implementation.
+pub trait GitBackend: Sized {
+    fn open(path: &Path) -> anyhow::Result<Self>;
+
+    /// Resolve `rev` (any revision expression a backend's revparse
+    /// understands) to the full hex id of the commit it points at, so
+    /// callers can use it as a stable cache key even when `rev` is something
+    /// that moves, like `HEAD` or a branch name.
+    fn resolve(&self, rev: &str) -> anyhow::Result<String>;
+
+    /// Read the UTF-8 contents of `path` as it existed at `rev`, where `rev`
+    /// is any revision expression a backend's revparse understands.
+    fn read_blob(&self, rev: &str, path: &str) -> anyhow::Result<String>;
+
+    /// Render the diff described by `req`.
+    fn diff(&self, req: DiffRequest) -> anyhow::Result<String>;
+
+    /// Read a raw string value out of the repository's own git config (e.g.
+    /// `.git/config`), as opposed to book.toml. Used to resolve project-wide
+    /// command defaults such as `git config --get git.context-lines`.
+    fn config_str(&self, key: &str) -> Option<String>;
+}
@@ -0,0 +1,105 @@
+use std::path::PathBuf;
+
+use moka::sync::Cache as MokaCache;
+
+type BlobKey = (PathBuf, String, String);
+type DiffKey = (PathBuf, String, String, String, Vec<String>);
+
+/// Process-lifetime cache for blob reads and diffs, keyed on the resolved
+/// commit id rather than the raw revision string so `HEAD` and its SHA share
+/// an entry. A preprocessor invocation is a single short-lived process, so a
+/// generously sized cache populated once in `run` is all that's needed; there
+/// is no eviction policy to tune beyond a capacity bound.
+pub struct Cache {
+    blobs: MokaCache<BlobKey, String>,
+    diffs: MokaCache<DiffKey, String>,
+}
+
+impl Cache {
+    pub fn new() -> Self {
+        Self {
+            blobs: MokaCache::new(1024),
+            diffs: MokaCache::new(1024),
+        }
+    }
+
+    pub fn get_or_try_insert_blob(
+        &self,
+        key: BlobKey,
+        compute: impl FnOnce() -> anyhow::Result<String>,
+    ) -> anyhow::Result<String> {
+        if let Some(cached) = self.blobs.get(&key) {
+            return Ok(cached);
+        }
+
+        let value = compute()?;
+        self.blobs.insert(key, value.clone());
+        Ok(value)
+    }
+
+    pub fn get_or_try_insert_diff(
+        &self,
+        key: DiffKey,
+        compute: impl FnOnce() -> anyhow::Result<String>,
+    ) -> anyhow::Result<String> {
+        if let Some(cached) = self.diffs.get(&key) {
+            return Ok(cached);
+        }
+
+        let value = compute()?;
+        self.diffs.insert(key, value.clone());
+        Ok(value)
+    }
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    #[test]
+    fn a_repeated_blob_key_reuses_the_cached_value_instead_of_recomputing() {
+        let cache = Cache::new();
+        let calls = Cell::new(0);
+        let key = (PathBuf::from("/repo"), "deadbeef".to_owned(), "src/lib.rs".to_owned());
+
+        for _ in 0..3 {
+            let value = cache
+                .get_or_try_insert_blob(key.clone(), || {
+                    calls.set(calls.get() + 1);
+                    Ok("fn main() {}".to_owned())
+                })
+                .unwrap();
+            assert_eq!(value, "fn main() {}");
+        }
+
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn a_different_oid_in_the_key_is_a_cache_miss() {
+        let cache = Cache::new();
+        let calls = Cell::new(0);
+
+        for oid in ["old-oid", "new-oid"] {
+            cache
+                .get_or_try_insert_blob(
+                    (PathBuf::from("/repo"), oid.to_owned(), "src/lib.rs".to_owned()),
+                    || {
+                        calls.set(calls.get() + 1);
+                        Ok(String::new())
+                    },
+                )
+                .unwrap();
+        }
+
+        assert_eq!(calls.get(), 2);
+    }
+}
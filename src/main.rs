@@ -1,3 +1,5 @@
+mod backend;
+mod cache;
 mod preprocessor;
 
 use clap::{Arg, ArgMatches, Command};
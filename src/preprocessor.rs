@@ -1,43 +1,199 @@
 use std::{
+    collections::HashMap,
     ops::{Bound, RangeBounds},
     path::Path,
     str::FromStr,
     sync::LazyLock,
 };
 
-use anyhow::{Context, anyhow};
-use git2::{DiffLineType, DiffOptions, Oid, Repository};
 use mdbook::{BookItem, preprocess::Preprocessor};
 use regex::{Captures, Regex};
 
+use crate::backend::{DefaultBackend, DiffRequest, GitBackend};
+use crate::cache::Cache;
+
 #[derive(Default, Debug)]
 pub struct Git {}
 
+/// A backend bound to the canonical path it was opened from, which doubles
+/// as the repo-identifying part of a cache key.
+struct OpenRepo {
+    backend: DefaultBackend,
+    path: std::path::PathBuf,
+}
+
+/// The prefix used to comment out lines that should be hidden from a shown
+/// code fence, resolved per file extension so non-Rust snippets don't end up
+/// commented out with an invalid `#`.
+#[derive(Debug)]
+struct HiddenPrefixes {
+    default: String,
+    by_extension: HashMap<String, String>,
+}
+
+impl HiddenPrefixes {
+    fn from_config(cfg: Option<&toml::value::Table>) -> Self {
+        let Some(cfg) = cfg else {
+            return Self::default();
+        };
+
+        let default = cfg
+            .get("hidden-prefix")
+            .and_then(|val| val.as_str())
+            .unwrap_or("# ")
+            .to_owned();
+
+        let by_extension = cfg
+            .get("hidden-prefix-by-extension")
+            .and_then(|val| val.as_table())
+            .map(|table| {
+                table
+                    .iter()
+                    .filter_map(|(ext, val)| Some((ext.clone(), val.as_str()?.to_owned())))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self {
+            default,
+            by_extension,
+        }
+    }
+
+    fn for_path(&self, path: &str) -> &str {
+        Path::new(path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(|ext| self.by_extension.get(ext))
+            .unwrap_or(&self.default)
+    }
+}
+
+impl Default for HiddenPrefixes {
+    fn default() -> Self {
+        Self {
+            default: "# ".to_owned(),
+            by_extension: HashMap::new(),
+        }
+    }
+}
+
+/// Project-wide command defaults, so an author doesn't have to repeat
+/// `-U5 -h` on every `{{ #git ... }}` invocation. Resolved once per run from
+/// `[preprocessor.git]` in book.toml, falling back to the repository's own
+/// git config (`git config --get git.<key>`) when book.toml doesn't set a
+/// value; inline per-command options still win over both.
+#[derive(Debug)]
+struct CommandDefaults {
+    context_lines: Option<u32>,
+    hide_deletions: bool,
+    default_revision: String,
+}
+
+impl CommandDefaults {
+    fn resolve(cfg: Option<&toml::value::Table>, repo: Option<&OpenRepo>) -> Self {
+        let config_int = |key: &str| -> Option<u32> {
+            cfg.and_then(|cfg| cfg.get(key))
+                .and_then(|val| val.as_integer())
+                .and_then(|val| u32::try_from(val).ok())
+                .or_else(|| {
+                    repo?
+                        .backend
+                        .config_str(&format!("git.{key}"))?
+                        .parse()
+                        .ok()
+                })
+        };
+
+        let config_bool = |key: &str, default: bool| -> bool {
+            cfg.and_then(|cfg| cfg.get(key))
+                .and_then(|val| val.as_bool())
+                .or_else(|| {
+                    repo?
+                        .backend
+                        .config_str(&format!("git.{key}"))?
+                        .parse()
+                        .ok()
+                })
+                .unwrap_or(default)
+        };
+
+        let default_revision = cfg
+            .and_then(|cfg| cfg.get("default-revision"))
+            .and_then(|val| val.as_str())
+            .map(ToOwned::to_owned)
+            .or_else(|| repo?.backend.config_str("git.default-revision"))
+            .unwrap_or_else(|| "HEAD".to_owned());
+
+        Self {
+            context_lines: config_int("context-lines"),
+            hide_deletions: config_bool("hide-deletions", false),
+            default_revision,
+        }
+    }
+}
+
+/// Apply [`CommandDefaults::default_revision`] when a command's revision
+/// argument was omitted, i.e. `{{ #git show :file }}` instead of
+/// `{{ #git show HEAD:file }}`.
+fn resolve_or_default<'a>(id: &'a str, defaults: &'a CommandDefaults) -> &'a str {
+    if id.is_empty() {
+        &defaults.default_revision
+    } else {
+        id
+    }
+}
+
+/// Resolve whether deletions (and the diff header) should be hidden, letting
+/// an inline `-h`/`-h=false` option override the project-wide default in
+/// either direction rather than only ever turning it on.
+fn resolve_hide_deletions(options: &[&str], default: bool) -> bool {
+    options
+        .iter()
+        .find_map(|item| {
+            item.strip_prefix("-h").map(|rest| match rest {
+                "=false" => false,
+                _ => true,
+            })
+        })
+        .unwrap_or(default)
+}
+
 impl Preprocessor for Git {
     fn name(&self) -> &str {
         "git"
     }
 
     // {{ #git diff [<options>] [commit_old] [commit_new] [file][:start:end] }}
-    // {{ #git show [commit]:[file][:start:end] }}
+    // {{ #git show [commit]:[file][:start:end] [--link] }}
+    // {{ #git link [commit]:[file][:start:end] }}
     fn run(
         &self,
         ctx: &mdbook::preprocess::PreprocessorContext,
         mut book: mdbook::book::Book,
     ) -> anyhow::Result<mdbook::book::Book> {
-        let default_repo = ctx
-            .config
-            .get_preprocessor(self.name())
+        let preprocessor_cfg = ctx.config.get_preprocessor(self.name());
+
+        let default_repo = preprocessor_cfg
             .and_then(|cfg| cfg.get("path"))
             .and_then(|val| val.as_str())
             .map(Path::new)
             .and_then(|path| ctx.root.join(path).canonicalize().ok())
             .map(|path| {
-                Repository::open(&path)
-                    .with_context(|| format!("Could not find repository at {:?}", path))
+                let backend = DefaultBackend::open(&path)?;
+                anyhow::Ok(OpenRepo { backend, path })
             })
             .transpose()?;
 
+        let hidden_prefixes = HiddenPrefixes::from_config(preprocessor_cfg);
+        let defaults = CommandDefaults::resolve(preprocessor_cfg, default_repo.as_ref());
+        let cache = Cache::new();
+
+        let repo_url = ctx
+            .config
+            .get("output.html.git-repository-url")
+            .and_then(|val| val.as_str());
+
         let src_dir = ctx.root.join(&ctx.config.book.src);
 
         book.for_each_mut(|section: &mut BookItem| {
@@ -48,8 +204,16 @@ impl Preprocessor for Git {
                         .map(|dir| src_dir.join(dir))
                         .expect("All book items have a parent");
 
-                    let content =
-                        replace_all(&ch.content, base, chapter_path, default_repo.as_ref());
+                    let content = replace_all(
+                        &ch.content,
+                        base,
+                        chapter_path,
+                        default_repo.as_ref(),
+                        &hidden_prefixes,
+                        &defaults,
+                        &cache,
+                        repo_url,
+                    );
                     ch.content = content;
                 }
             }
@@ -67,7 +231,11 @@ fn replace_all(
     s: &str,
     _path: impl AsRef<Path>,
     _source: impl AsRef<Path>,
-    default_repo: Option<&Repository>,
+    default_repo: Option<&OpenRepo>,
+    hidden_prefixes: &HiddenPrefixes,
+    defaults: &CommandDefaults,
+    cache: &Cache,
+    repo_url: Option<&str>,
 ) -> String {
     let Some(repo) = default_repo else {
         return s.to_owned();
@@ -83,8 +251,36 @@ fn replace_all(
         replaced.push_str(&s[previous_end_index..start]);
 
         match typ {
-            GitType::Show { id, path, ranges } => {
-                if let Ok(contents) = git_show(id, path, ranges, repo) {
+            GitType::Show {
+                id,
+                path,
+                ranges,
+                link,
+            } => {
+                let id = resolve_or_default(id, defaults);
+
+                if let Ok(mut contents) =
+                    git_show(id, path, ranges.clone(), repo, hidden_prefixes, cache)
+                {
+                    if link {
+                        if let Some(remote_link) = repo_url
+                            .and_then(|url| git_link(id, path, &ranges, repo, url).ok())
+                        {
+                            contents.push_str("\n\n");
+                            contents.push_str(&remote_link);
+                        }
+                    }
+
+                    replaced.push_str(&contents);
+                    previous_end_index = end;
+                }
+            }
+            GitType::Link { id, path, ranges } => {
+                let id = resolve_or_default(id, defaults);
+
+                if let Some(contents) =
+                    repo_url.and_then(|url| git_link(id, path, &ranges, repo, url).ok())
+                {
                     replaced.push_str(&contents);
                     previous_end_index = end;
                 }
@@ -96,7 +292,20 @@ fn replace_all(
                 ranges,
                 options,
             } => {
-                if let Ok(contents) = git_diff(old, new, path, ranges, options, repo) {
+                // `old`/`new` come from whitespace-split tokens and so can
+                // never be empty; `default-revision` only applies to
+                // `show`/`link`, where an id can be omitted before the `:`.
+                if let Ok(contents) = git_diff(
+                    old,
+                    new,
+                    path,
+                    ranges,
+                    options,
+                    repo,
+                    hidden_prefixes,
+                    defaults,
+                    cache,
+                ) {
                     replaced.push_str(&contents);
                     previous_end_index = end;
                 }
@@ -112,30 +321,72 @@ fn git_show(
     id: &str,
     path: &str,
     ranges: Vec<impl RangeBounds<usize>>,
-    repo: &Repository,
+    repo: &OpenRepo,
+    hidden_prefixes: &HiddenPrefixes,
+    cache: &Cache,
+) -> anyhow::Result<String> {
+    let oid = repo.backend.resolve(id)?;
+    let contents = cache.get_or_try_insert_blob(
+        (repo.path.clone(), oid, path.to_owned()),
+        || repo.backend.read_blob(id, path),
+    )?;
+    let prefix = hidden_prefixes.for_path(path);
+
+    Ok(take_lines_comment_out_rest(&contents, ranges, prefix))
+}
+
+/// Build a markdown link to `path` at `id` on the configured remote, anchored
+/// to the first line range if one was given. Degrades to an error (and thus
+/// no link at all) when `id` doesn't resolve, since a dangling link is worse
+/// than a missing one.
+fn git_link(
+    id: &str,
+    path: &str,
+    ranges: &[(Bound<usize>, Bound<usize>)],
+    repo: &OpenRepo,
+    repo_url: &str,
 ) -> anyhow::Result<String> {
-    let id = Oid::from_str(id)?;
-    let commit = repo.find_commit(id)?;
+    let sha = repo.backend.resolve(id)?;
+    let repo_url = repo_url.trim_end_matches('/');
 
-    let tree = commit.tree()?;
-    let entry = tree.get_path(std::path::Path::new(path))?;
+    let mut link = format!("{repo_url}/blob/{sha}/{path}");
 
-    let object = entry.to_object(&repo)?;
-    let blob = object
-        .as_blob()
-        .ok_or_else(|| anyhow!("Commit does not contain this file."))?;
+    if let Some((start, end)) = line_anchor(ranges) {
+        link.push_str(&format!("#L{start}-L{end}"));
+    }
 
-    std::str::from_utf8(blob.content())
-        .map(|s| take_lines_comment_out_rest(s, ranges))
-        .map_err(Into::into)
+    Ok(format!("[view on remote]({link})"))
 }
 
-pub fn take_lines_comment_out_rest(s: &str, ranges: Vec<impl RangeBounds<usize>>) -> String {
+/// Turn the first (0-indexed) line range into a 1-indexed, inclusive
+/// `(start, end)` pair suitable for a `#L<start>-L<end>` anchor, or `None`
+/// for an unbounded range, which has no single line to anchor to.
+fn line_anchor(ranges: &[(Bound<usize>, Bound<usize>)]) -> Option<(usize, usize)> {
+    let (start, end) = ranges.first()?;
+
+    let start = match start {
+        Bound::Included(n) | Bound::Excluded(n) => *n + 1,
+        Bound::Unbounded => return None,
+    };
+    let end = match end {
+        Bound::Included(n) => *n + 1,
+        Bound::Excluded(n) => *n,
+        Bound::Unbounded => return None,
+    };
+
+    Some((start, end))
+}
+
+pub fn take_lines_comment_out_rest(
+    s: &str,
+    ranges: Vec<impl RangeBounds<usize>>,
+    prefix: &str,
+) -> String {
     let mut lines: Vec<String> = s.lines().map(ToOwned::to_owned).collect();
 
     for (i, line) in lines.iter_mut().enumerate() {
-        if !line.starts_with("# ") && ranges.iter().all(|range| !range.contains(&i)) {
-            *line = format!("# {line}");
+        if !line.starts_with(prefix) && ranges.iter().all(|range| !range.contains(&i)) {
+            *line = format!("{prefix}{line}");
         }
     }
 
@@ -201,66 +452,51 @@ fn git_diff(
     path: &str,
     ranges: Vec<impl RangeBounds<usize>>,
     options: Vec<&str>,
-    repo: &Repository,
+    repo: &OpenRepo,
+    hidden_prefixes: &HiddenPrefixes,
+    defaults: &CommandDefaults,
+    cache: &Cache,
 ) -> anyhow::Result<String> {
-    let old_commit = repo.find_commit(Oid::from_str(old)?)?;
-    let new_commit = repo.find_commit(Oid::from_str(new)?)?;
-
-    let old_tree = old_commit.tree()?;
-    let new_tree = new_commit.tree()?;
-
-    let mut diff_opts = DiffOptions::new();
-    diff_opts.pathspec(path);
-
-    // handle options
-    if let Some(number_context_lines) = options
+    // handle options, falling back to the resolved project-wide defaults
+    let context_lines = options
         .iter()
         .find_map(|item| item.starts_with("-U").then(|| item[2..].parse()))
         .transpose()?
-    {
-        diff_opts.context_lines(number_context_lines);
-    }
-
-    // special non-git option
-    let hide_header_and_deletion = options.iter().any(|item| item.starts_with("-h"));
-
-    let diff = repo.diff_tree_to_tree(Some(&old_tree), Some(&new_tree), Some(&mut diff_opts))?;
-    let mut str = String::new();
-
-    diff.print(git2::DiffFormat::Patch, |_delta, _hunk, line| {
-        let mut content = std::str::from_utf8(line.content())
-            .expect("utf8 expected")
-            .to_owned();
-
-        content = content
-            .lines()
-            .map(|content| {
-                format!(
-                    "{}{}{content}\n",
-                    (hide_header_and_deletion
-                        && !matches!(
-                            line.origin_value(),
-                            DiffLineType::Addition | DiffLineType::Context
-                        ))
-                    .then(|| "# ")
-                    .unwrap_or_default(),
-                    (matches!(
-                        line.origin_value(),
-                        DiffLineType::Addition | DiffLineType::Deletion | DiffLineType::Context
-                    ))
-                    .then(|| line.origin().to_string())
-                    .unwrap_or_default()
-                )
-            })
-            .collect::<Vec<String>>()
-            .join("");
-
-        str.push_str(&content);
-
-        true
+        .or(defaults.context_lines);
+
+    // special non-git options
+    let hide_deletions = resolve_hide_deletions(&options, defaults.hide_deletions);
+    let stat = options.iter().any(|item| *item == "--stat");
+    let prefix = hidden_prefixes.for_path(path);
+
+    let old_oid = repo.backend.resolve(old)?;
+    let new_oid = repo.backend.resolve(new)?;
+
+    let cache_key = (
+        repo.path.clone(),
+        old_oid,
+        new_oid,
+        path.to_owned(),
+        options.iter().map(|opt| opt.to_string()).collect(),
+    );
+
+    let contents = cache.get_or_try_insert_diff(cache_key, || {
+        repo.backend.diff(DiffRequest {
+            old,
+            new,
+            path,
+            context_lines,
+            hide_deletions,
+            hidden_prefix: prefix,
+            stat,
+        })
     })?;
 
-    Ok(take_lines_comment_out_rest(&str, ranges))
+    if stat {
+        return Ok(contents);
+    }
+
+    Ok(take_lines_comment_out_rest(&contents, ranges, prefix))
 }
 
 #[derive(Debug)]
@@ -286,6 +522,12 @@ enum GitType<'a> {
         id: &'a str,
         path: &'a str,
         ranges: Vec<(Bound<usize>, Bound<usize>)>,
+        link: bool,
+    },
+    Link {
+        id: &'a str,
+        path: &'a str,
+        ranges: Vec<(Bound<usize>, Bound<usize>)>,
     },
     Diff {
         old: &'a str,
@@ -311,12 +553,26 @@ impl<'a> TryFrom<Captures<'a>> for GitType<'a> {
         subcmd.sort_unstable_by(|a, b| a.starts_with('-').cmp(&b.starts_with('-')));
 
         let cmd = match subcmd.as_slice() {
-            &["show", id_and_path_and_ranges, ..] => id_and_path_and_ranges
+            &["show", id_and_path_and_ranges, ref options @ ..] => id_and_path_and_ranges
+                .split_once(":")
+                .map(|(id, path_and_ranges)| {
+                    let (path, ranges) = parse_path_and_ranges(path_and_ranges);
+                    let link = options.iter().any(|item| *item == "--link");
+
+                    (ranges.len() != 0).then(|| GitType::Show {
+                        id,
+                        path,
+                        ranges,
+                        link,
+                    })
+                })
+                .flatten(),
+            &["link", id_and_path_and_ranges, ..] => id_and_path_and_ranges
                 .split_once(":")
                 .map(|(id, path_and_ranges)| {
                     let (path, ranges) = parse_path_and_ranges(path_and_ranges);
 
-                    (ranges.len() != 0).then(|| GitType::Show { id, path, ranges })
+                    (ranges.len() != 0).then(|| GitType::Link { id, path, ranges })
                 })
                 .flatten(),
             &["diff", old, new, path_and_ranges, ref options @ ..] => {
@@ -357,3 +613,68 @@ fn find_git_cmds(contents: &str) -> impl Iterator<Item = Captures<'_>> {
 
     RE.captures_iter(contents)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inline_hide_deletions_turns_on_over_a_false_default() {
+        assert!(resolve_hide_deletions(&["-h"], false));
+    }
+
+    #[test]
+    fn inline_hide_deletions_can_turn_off_a_true_default() {
+        assert!(!resolve_hide_deletions(&["-h=false"], true));
+    }
+
+    #[test]
+    fn missing_inline_option_falls_back_to_the_default() {
+        assert!(resolve_hide_deletions(&[], true));
+        assert!(!resolve_hide_deletions(&[], false));
+    }
+
+    #[test]
+    fn command_defaults_reads_book_toml_over_the_hardcoded_fallback() {
+        let mut table = toml::value::Table::new();
+        table.insert("context-lines".into(), toml::Value::Integer(7));
+        table.insert("hide-deletions".into(), toml::Value::Boolean(true));
+        table.insert(
+            "default-revision".into(),
+            toml::Value::String("main".into()),
+        );
+
+        let defaults = CommandDefaults::resolve(Some(&table), None);
+
+        assert_eq!(defaults.context_lines, Some(7));
+        assert!(defaults.hide_deletions);
+        assert_eq!(defaults.default_revision, "main");
+    }
+
+    #[test]
+    fn command_defaults_fall_back_when_book_toml_is_absent() {
+        let defaults = CommandDefaults::resolve(None, None);
+
+        assert_eq!(defaults.context_lines, None);
+        assert!(!defaults.hide_deletions);
+        assert_eq!(defaults.default_revision, "HEAD");
+    }
+
+    #[test]
+    fn line_anchor_on_a_single_line_range_anchors_to_that_line_twice() {
+        let ranges = vec![(Bound::Included(4), Bound::Included(4))];
+        assert_eq!(line_anchor(&ranges), Some((5, 5)));
+    }
+
+    #[test]
+    fn line_anchor_on_an_inclusive_start_end_range_is_1_indexed() {
+        let ranges = vec![(Bound::Included(1), Bound::Excluded(4))];
+        assert_eq!(line_anchor(&ranges), Some((2, 4)));
+    }
+
+    #[test]
+    fn line_anchor_on_an_unbounded_range_has_nothing_to_anchor_to() {
+        let ranges = vec![(Bound::Unbounded, Bound::Unbounded)];
+        assert_eq!(line_anchor(&ranges), None);
+    }
+}